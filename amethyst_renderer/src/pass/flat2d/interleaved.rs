@@ -1,17 +1,22 @@
 //! Flat forward drawing pass that mimics a blit.
 
 use derivative::Derivative;
+use gfx::format::{ChannelType, Format, SurfaceType};
+use gfx::handle::Buffer;
 use gfx::pso::buffer::ElemStride;
-use gfx_core::state::{Blend, ColorMask};
+use gfx_core::state::{Blend, BlendChannel, BlendValue, ColorMask, Equation, Factor};
 use glsl_layout::Uniform;
 use log::warn;
 use std::marker::PhantomData;
 
+use std::collections::HashMap;
+
 use amethyst_assets::{AssetStorage, Handle};
 use amethyst_core::{
-    nalgebra::{alga::general::SubsetOf, convert, one, zero, Real, Vector4},
-    specs::prelude::{Join, Read, ReadStorage},
+    nalgebra::{alga::general::SubsetOf, convert, one, zero, Matrix4, Real, Vector4},
+    specs::prelude::{Component, DenseVecStorage, Join, Read, ReadStorage, System, Write},
     transform::Transform,
+    Transparent,
 };
 use amethyst_error::Error;
 
@@ -29,20 +34,66 @@ use crate::{
     sprite::{Flipped, SpriteRender, SpriteSheet},
     sprite_visibility::SpriteVisibility,
     tex::{Texture, TextureHandle},
-    types::{Encoder, Factory, Slice},
+    types::{Encoder, Factory, Resources, Slice},
     vertex::{Attributes, Query, VertexFormat},
     Color, Rgba,
 };
 
 use super::*;
 
+/// Texture slot names for the YUV fragment shader, bound in order by
+/// `TextureBatch::encode` when flushing a `TextureDrawData::Yuv` batch.
+const TEXTURES_YUV: [&str; 3] = ["y_texture", "u_texture", "v_texture"];
+
+/// Samples the Y/U/V planes bound via `TEXTURES_YUV` and converts to RGB
+/// in-shader, selecting the conversion matrix via the `yuv_matrix` uniform
+/// (`0` = BT.601, `1` = BT.709). This follows WebRender's `brush_yuv_image`
+/// approach so decoded video frames can be drawn without a CPU-side
+/// YUV->RGB conversion pass.
+static FRAG_SRC_YUV: &[u8] = br#"#version 150 core
+
+in vec2 v_uv;
+in vec4 v_color;
+
+uniform sampler2D y_texture;
+uniform sampler2D u_texture;
+uniform sampler2D v_texture;
+uniform int yuv_matrix;
+
+out vec4 out_color;
+
+void main() {
+    float y = texture(y_texture, v_uv).r;
+    float u = texture(u_texture, v_uv).r - 0.5;
+    float v = texture(v_texture, v_uv).r - 0.5;
+
+    float r;
+    float g;
+    float b;
+
+    if (yuv_matrix == 1) {
+        // BT.709
+        r = y + 1.5748 * v;
+        g = y - 0.1873 * u - 0.4681 * v;
+        b = y + 1.8556 * u;
+    } else {
+        // BT.601
+        r = y + 1.402 * v;
+        g = y - 0.344 * u - 0.714 * v;
+        b = y + 1.772 * u;
+    }
+
+    out_color = vec4(r, g, b, 1.0) * v_color;
+}
+"#;
+
 /// Draws sprites on a 2D quad.
 ///
 /// # Type Parameters:
 ///
 /// * `N`: `RealBound` (f32, f64)
-#[derive(Derivative, Clone, Debug)]
-#[derivative(Default(bound = "Self: Pass"))]
+#[derive(Derivative)]
+#[derivative(Debug, Default(bound = "Self: Pass"))]
 pub struct DrawFlat2D<N>
 where
     N: Real,
@@ -50,6 +101,15 @@ where
     #[derivative(Default(value = "default_transparency()"))]
     transparency: Option<(ColorMask, Blend, Option<DepthMode>)>,
     batch: TextureBatch<N>,
+    #[derivative(Debug = "ignore")]
+    blend_effects: HashMap<BlendMode, Effect>,
+    #[derivative(Debug = "ignore")]
+    yuv_effect: Option<Effect>,
+    /// Mirrors `blend_effects`, but built from `FRAG_SRC_YUV` so a `YuvSprite`
+    /// carrying a non-`Alpha` `BlendMode` still gets its blend state honored
+    /// instead of silently falling back to `yuv_effect`.
+    #[derivative(Debug = "ignore")]
+    yuv_blend_effects: HashMap<BlendMode, Effect>,
     _pd: PhantomData<N>,
 }
 
@@ -110,6 +170,10 @@ impl<'a, N: Real> PassData<'a> for DrawFlat2D<N> {
         ReadStorage<'a, Flipped>,
         ReadStorage<'a, MeshHandle>,
         ReadStorage<'a, Rgba>,
+        ReadStorage<'a, Transparent>,
+        ReadStorage<'a, BlendMode>,
+        ReadStorage<'a, YuvSprite>,
+        ReadStorage<'a, NineSlice>,
     );
 }
 
@@ -131,7 +195,101 @@ impl<N: Real> Pass for DrawFlat2D<N> {
             Some((mask, blend, depth)) => builder.with_blended_output("color", mask, blend, depth),
             None => builder.with_output("color", Some(DepthMode::LessEqualWrite)),
         };
-        builder.build()
+        let default_effect = builder.build()?;
+
+        // `Alpha` reuses the pass-wide `self.transparency` setting compiled
+        // above; the other presets each need their own blend state baked into
+        // their own pipeline, so precompile one `Effect` per preset here,
+        // once, rather than recompiling per frame.
+        self.blend_effects.clear();
+        for &mode in &[BlendMode::Add, BlendMode::Multiply, BlendMode::Screen] {
+            let mut builder = effect.simple(VERT_SRC, FRAG_SRC);
+            builder
+                .without_back_face_culling()
+                .with_raw_constant_buffer(
+                    "ViewArgs",
+                    mem::size_of::<<ViewArgs as Uniform>::Std140>(),
+                    1,
+                )
+                .with_raw_vertex_buffer(Self::attributes(), SpriteInstance::size() as ElemStride, 1);
+            setup_textures(&mut builder, &TEXTURES);
+            builder.with_blended_output("color", ColorMask::all(), mode.blend(), None);
+            self.blend_effects.insert(mode, builder.build()?);
+        }
+
+        // `Normal` renders fully opaque, so unlike the blended presets above it
+        // gets a pipeline built with `with_output`, not `with_blended_output`.
+        let mut normal_builder = effect.simple(VERT_SRC, FRAG_SRC);
+        normal_builder
+            .without_back_face_culling()
+            .with_raw_constant_buffer(
+                "ViewArgs",
+                mem::size_of::<<ViewArgs as Uniform>::Std140>(),
+                1,
+            )
+            .with_raw_vertex_buffer(Self::attributes(), SpriteInstance::size() as ElemStride, 1);
+        setup_textures(&mut normal_builder, &TEXTURES);
+        normal_builder.with_output("color", Some(DepthMode::LessEqualWrite));
+        self.blend_effects
+            .insert(BlendMode::Normal, normal_builder.build()?);
+
+        // Second `Effect`, compiled from the YUV fragment shader, dispatched
+        // for any batch made up of `TextureDrawData::Yuv` quads.
+        let mut yuv_builder = effect.simple(VERT_SRC, FRAG_SRC_YUV);
+        yuv_builder
+            .without_back_face_culling()
+            .with_raw_constant_buffer(
+                "ViewArgs",
+                mem::size_of::<<ViewArgs as Uniform>::Std140>(),
+                1,
+            )
+            .with_raw_vertex_buffer(Self::attributes(), SpriteInstance::size() as ElemStride, 1)
+            .with_raw_global("yuv_matrix", Format(SurfaceType::R32, ChannelType::Int));
+        setup_textures(&mut yuv_builder, &TEXTURES_YUV);
+        match self.transparency {
+            Some((mask, blend, depth)) => {
+                yuv_builder.with_blended_output("color", mask, blend, depth)
+            }
+            None => yuv_builder.with_output("color", Some(DepthMode::LessEqualWrite)),
+        };
+        self.yuv_effect = Some(yuv_builder.build()?);
+
+        // Mirrors the `blend_effects` loop above, so a `YuvSprite` with a
+        // non-`Alpha` `BlendMode` gets its blend state honored too, instead of
+        // always drawing through the pass-wide `yuv_effect`.
+        self.yuv_blend_effects.clear();
+        for &mode in &[BlendMode::Add, BlendMode::Multiply, BlendMode::Screen] {
+            let mut builder = effect.simple(VERT_SRC, FRAG_SRC_YUV);
+            builder
+                .without_back_face_culling()
+                .with_raw_constant_buffer(
+                    "ViewArgs",
+                    mem::size_of::<<ViewArgs as Uniform>::Std140>(),
+                    1,
+                )
+                .with_raw_vertex_buffer(Self::attributes(), SpriteInstance::size() as ElemStride, 1)
+                .with_raw_global("yuv_matrix", Format(SurfaceType::R32, ChannelType::Int));
+            setup_textures(&mut builder, &TEXTURES_YUV);
+            builder.with_blended_output("color", ColorMask::all(), mode.blend(), None);
+            self.yuv_blend_effects.insert(mode, builder.build()?);
+        }
+
+        let mut normal_yuv_builder = effect.simple(VERT_SRC, FRAG_SRC_YUV);
+        normal_yuv_builder
+            .without_back_face_culling()
+            .with_raw_constant_buffer(
+                "ViewArgs",
+                mem::size_of::<<ViewArgs as Uniform>::Std140>(),
+                1,
+            )
+            .with_raw_vertex_buffer(Self::attributes(), SpriteInstance::size() as ElemStride, 1)
+            .with_raw_global("yuv_matrix", Format(SurfaceType::R32, ChannelType::Int));
+        setup_textures(&mut normal_yuv_builder, &TEXTURES_YUV);
+        normal_yuv_builder.with_output("color", Some(DepthMode::LessEqualWrite));
+        self.yuv_blend_effects
+            .insert(BlendMode::Normal, normal_yuv_builder.build()?);
+
+        Ok(default_effect)
     }
 
     fn apply<'a, 'b: 'a>(
@@ -153,17 +311,23 @@ impl<N: Real> Pass for DrawFlat2D<N> {
             flipped,
             mesh,
             rgba,
+            transparent,
+            blend_mode,
+            yuv_sprite,
+            nine_slice,
         ): <Self as PassData<'a>>::Data,
     ) {
         let camera = get_camera(active, &camera, &transform);
 
         match visibility {
             None => {
-                for (sprite_render, transform, flipped, rgba, _, _) in (
+                for (sprite_render, transform, flipped, rgba, transparent, blend_mode, _, _) in (
                     &sprite_render,
                     &transform,
                     flipped.maybe(),
                     rgba.maybe(),
+                    transparent.maybe(),
+                    blend_mode.maybe(),
                     !&hidden,
                     !&hidden_prop,
                 )
@@ -174,16 +338,32 @@ impl<N: Real> Pass for DrawFlat2D<N> {
                         Some(transform),
                         flipped,
                         rgba,
+                        transparent,
+                        blend_mode,
                         &sprite_sheet_storage,
                         &tex_storage,
                     );
                 }
 
-                for (image_render, transform, flipped, rgba, _, _, _) in (
+                for (
+                    image_render,
+                    transform,
+                    flipped,
+                    rgba,
+                    transparent,
+                    blend_mode,
+                    nine_slice,
+                    _,
+                    _,
+                    _,
+                ) in (
                     &texture_handle,
                     &transform,
                     flipped.maybe(),
                     rgba.maybe(),
+                    transparent.maybe(),
+                    blend_mode.maybe(),
+                    nine_slice.maybe(),
                     !&hidden,
                     !&hidden_prop,
                     !&mesh,
@@ -195,18 +375,46 @@ impl<N: Real> Pass for DrawFlat2D<N> {
                         Some(transform),
                         flipped,
                         rgba,
+                        transparent,
+                        blend_mode,
+                        nine_slice,
                         &tex_storage,
                     );
                 }
 
-                self.batch.sort();
+                for (yuv_sprite, transform, flipped, rgba, transparent, blend_mode, _, _) in (
+                    &yuv_sprite,
+                    &transform,
+                    flipped.maybe(),
+                    rgba.maybe(),
+                    transparent.maybe(),
+                    blend_mode.maybe(),
+                    !&hidden,
+                    !&hidden_prop,
+                )
+                    .join()
+                {
+                    self.batch.add_yuv(
+                        yuv_sprite,
+                        Some(transform),
+                        flipped,
+                        rgba,
+                        transparent,
+                        blend_mode,
+                        &tex_storage,
+                    );
+                }
+
+                self.batch.sort(camera);
             }
             Some(ref visibility) => {
-                for (sprite_render, transform, flipped, rgba, _) in (
+                for (sprite_render, transform, flipped, rgba, transparent, blend_mode, _) in (
                     &sprite_render,
                     &transform,
                     flipped.maybe(),
                     rgba.maybe(),
+                    transparent.maybe(),
+                    blend_mode.maybe(),
                     &visibility.visible_unordered,
                 )
                     .join()
@@ -216,16 +424,31 @@ impl<N: Real> Pass for DrawFlat2D<N> {
                         Some(transform),
                         flipped,
                         rgba,
+                        transparent,
+                        blend_mode,
                         &sprite_sheet_storage,
                         &tex_storage,
                     );
                 }
 
-                for (image_render, transform, flipped, rgba, _, _) in (
+                for (
+                    image_render,
+                    transform,
+                    flipped,
+                    rgba,
+                    transparent,
+                    blend_mode,
+                    nine_slice,
+                    _,
+                    _,
+                ) in (
                     &texture_handle,
                     &transform,
                     flipped.maybe(),
                     rgba.maybe(),
+                    transparent.maybe(),
+                    blend_mode.maybe(),
+                    nine_slice.maybe(),
                     &visibility.visible_unordered,
                     !&mesh,
                 )
@@ -236,12 +459,38 @@ impl<N: Real> Pass for DrawFlat2D<N> {
                         Some(transform),
                         flipped,
                         rgba,
+                        transparent,
+                        blend_mode,
+                        nine_slice,
+                        &tex_storage,
+                    );
+                }
+
+                for (yuv_sprite, transform, flipped, rgba, transparent, blend_mode, _, _) in (
+                    &yuv_sprite,
+                    &transform,
+                    flipped.maybe(),
+                    rgba.maybe(),
+                    transparent.maybe(),
+                    blend_mode.maybe(),
+                    &visibility.visible_unordered,
+                    !&mesh,
+                )
+                    .join()
+                {
+                    self.batch.add_yuv(
+                        yuv_sprite,
+                        Some(transform),
+                        flipped,
+                        rgba,
+                        transparent,
+                        blend_mode,
                         &tex_storage,
                     );
                 }
 
                 // We are free to optimize the order of the opaque sprites.
-                self.batch.sort();
+                self.batch.sort(camera);
 
                 for entity in &visibility.visible_ordered {
                     if let Some(sprite_render) = sprite_render.get(*entity) {
@@ -250,6 +499,8 @@ impl<N: Real> Pass for DrawFlat2D<N> {
                             transform.get(*entity),
                             flipped.get(*entity),
                             rgba.get(*entity),
+                            transparent.get(*entity),
+                            blend_mode.get(*entity),
                             &sprite_sheet_storage,
                             &tex_storage,
                         );
@@ -259,6 +510,19 @@ impl<N: Real> Pass for DrawFlat2D<N> {
                             transform.get(*entity),
                             flipped.get(*entity),
                             rgba.get(*entity),
+                            transparent.get(*entity),
+                            blend_mode.get(*entity),
+                            nine_slice.get(*entity),
+                            &tex_storage,
+                        )
+                    } else if let Some(yuv_sprite) = yuv_sprite.get(*entity) {
+                        self.batch.add_yuv(
+                            yuv_sprite,
+                            transform.get(*entity),
+                            flipped.get(*entity),
+                            rgba.get(*entity),
+                            transparent.get(*entity),
+                            blend_mode.get(*entity),
                             &tex_storage,
                         )
                     }
@@ -269,6 +533,9 @@ impl<N: Real> Pass for DrawFlat2D<N> {
             encoder,
             &mut factory,
             effect,
+            &mut self.blend_effects,
+            self.yuv_effect.as_mut(),
+            &mut self.yuv_blend_effects,
             camera,
             &sprite_sheet_storage,
             &tex_storage,
@@ -277,6 +544,626 @@ impl<N: Real> Pass for DrawFlat2D<N> {
     }
 }
 
+/// Pre-computed per-instance data, matching the 15 floats `TextureBatch::encode`
+/// builds inline today, plus the resolved texture and whether the quad is
+/// transparent (which decides where it goes in the draw order).
+///
+/// Produced off the render thread by the `*Flat2DAssetEncoder` systems below and
+/// drained by `DrawFlat2DEncoded`, so the asset lookups and `global_matrix()`
+/// math can run in parallel with the rest of the dispatch instead of blocking
+/// the render pass.
+#[derive(Clone, Debug)]
+pub struct Flat2DData {
+    dir_x: [f32; 2],
+    dir_y: [f32; 2],
+    pos: [f32; 2],
+    uv: [f32; 4],
+    depth: f32,
+    rgba: [f32; 4],
+    texture_handle: Handle<Texture>,
+    /// Entity translation (`global_matrix().column(3)`), kept separately from
+    /// the offset `pos`/`depth` above so `encoded_view_space_depth` can sort
+    /// on the same world point `view_space_depth` uses for `TextureBatch`.
+    translation: [f32; 3],
+    /// Whether this quad is tagged `Transparent` or its resolved `rgba` has
+    /// any transparency. Used by `DrawFlat2DEncoded::apply` to route it into
+    /// the back-to-front sorted run instead of the texture-id-sorted opaque
+    /// one, same as `TextureDrawData::is_transparent` does for `TextureBatch`.
+    transparent: bool,
+}
+
+/// Resource holding the `Flat2DData` instances encoded by the `*Flat2DAssetEncoder`
+/// systems during the normal dispatch, ready to be drained by `DrawFlat2DEncoded`.
+#[derive(Default)]
+pub struct EncodingBuffer {
+    data: Vec<Flat2DData>,
+}
+
+fn encode_sprite<N: Real>(
+    render: &SpriteRender,
+    transform: &Transform<N>,
+    flipped: Option<&Flipped>,
+    rgba: Option<&Rgba>,
+    transparent: Option<&Transparent>,
+    sprite_sheet_storage: &AssetStorage<SpriteSheet>,
+    tex_storage: &AssetStorage<Texture>,
+) -> Option<Flat2DData> {
+    let sprite_sheet = sprite_sheet_storage.get(&render.sprite_sheet)?;
+
+    if tex_storage.get(&sprite_sheet.texture).is_none() {
+        warn!(
+            "Texture not loaded for texture: `{:?}`.",
+            sprite_sheet.texture
+        );
+        return None;
+    }
+
+    let (flip_horizontal, flip_vertical) = match flipped {
+        Some(Flipped::Horizontal) => (true, false),
+        Some(Flipped::Vertical) => (false, true),
+        Some(Flipped::Both) => (true, true),
+        _ => (false, false),
+    };
+
+    let sprite_data = &sprite_sheet.sprites[render.sprite_number];
+
+    let tex_coords = &sprite_data.tex_coords;
+    let (uv_left, uv_right) = if flip_horizontal {
+        (tex_coords.right, tex_coords.left)
+    } else {
+        (tex_coords.left, tex_coords.right)
+    };
+    let (uv_bottom, uv_top) = if flip_vertical {
+        (tex_coords.top, tex_coords.bottom)
+    } else {
+        (tex_coords.bottom, tex_coords.top)
+    };
+
+    let global_matrix = &transform.global_matrix();
+
+    let dir_x = global_matrix.column(0) * sprite_data.width;
+    let dir_y = global_matrix.column(1) * sprite_data.height;
+
+    // The offsets are negated to shift the sprite left and down relative to the entity, in
+    // regards to pivot points. This is the convention adopted in:
+    //
+    // * libgdx: <https://gamedev.stackexchange.com/q/22553>
+    // * godot: <https://godotengine.org/qa/9784>
+    let pos = global_matrix * Vector4::new(-sprite_data.offsets[0], -sprite_data.offsets[1], 0.0, 1.0);
+    let translation = global_matrix.column(3);
+
+    let rgba = rgba.cloned().unwrap_or(Rgba::WHITE);
+
+    Some(Flat2DData {
+        dir_x: [dir_x.x, dir_x.y],
+        dir_y: [dir_y.x, dir_y.y],
+        pos: [pos.x, pos.y],
+        uv: [uv_left, uv_right, uv_bottom, uv_top],
+        depth: pos.z,
+        rgba: [rgba.0, rgba.1, rgba.2, rgba.3],
+        texture_handle: sprite_sheet.texture.clone(),
+        translation: [convert(translation.x), convert(translation.y), convert(translation.z)],
+        transparent: transparent.is_some() || rgba.3 < 1.0,
+    })
+}
+
+fn encode_image<N: Real>(
+    texture_handle: &TextureHandle,
+    transform: &Transform<N>,
+    flipped: Option<&Flipped>,
+    rgba: Option<&Rgba>,
+    transparent: Option<&Transparent>,
+    tex_storage: &AssetStorage<Texture>,
+) -> Option<Flat2DData> {
+    let texture_dims = match tex_storage.get(texture_handle) {
+        Some(tex) => tex.size(),
+        None => {
+            warn!("Texture not loaded for texture: `{:?}`.", texture_handle);
+            return None;
+        }
+    };
+
+    let (flip_horizontal, flip_vertical) = match flipped {
+        Some(Flipped::Horizontal) => (true, false),
+        Some(Flipped::Vertical) => (false, true),
+        Some(Flipped::Both) => (true, true),
+        _ => (false, false),
+    };
+
+    let (uv_left, uv_right) = if flip_horizontal { (1.0, 0.0) } else { (0.0, 1.0) };
+    let (uv_bottom, uv_top) = if flip_vertical { (1.0, 0.0) } else { (0.0, 1.0) };
+
+    let global_matrix = &transform.global_matrix();
+
+    let dir_x = global_matrix.column(0) * (texture_dims.0 as f32);
+    let dir_y = global_matrix.column(1) * (texture_dims.1 as f32);
+
+    let pos = global_matrix * Vector4::<N>::new(one(), one(), zero(), one());
+    let translation = global_matrix.column(3);
+
+    let rgba = rgba.cloned().unwrap_or(Rgba::WHITE);
+
+    Some(Flat2DData {
+        dir_x: [dir_x.x, dir_x.y],
+        dir_y: [dir_y.x, dir_y.y],
+        pos: [pos.x, pos.y],
+        uv: [uv_left, uv_right, uv_bottom, uv_top],
+        depth: pos.z,
+        rgba: [rgba.0, rgba.1, rgba.2, rgba.3],
+        texture_handle: texture_handle.clone(),
+        translation: [convert(translation.x), convert(translation.y), convert(translation.z)],
+        transparent: transparent.is_some() || rgba.3 < 1.0,
+    })
+}
+
+/// Encodes `SpriteRender` entities into the shared `EncodingBuffer`.
+///
+/// Runs as an ordinary `System` during the normal dispatch (and therefore in
+/// parallel with other systems), moving the sprite sheet/texture lookups and
+/// `global_matrix()` math out of `DrawFlat2DEncoded::apply`.
+#[derive(Derivative)]
+#[derivative(Default(bound = ""))]
+pub struct SpriteFlat2DAssetEncoder<N> {
+    _pd: PhantomData<N>,
+}
+
+impl<'a, N: Real> System<'a> for SpriteFlat2DAssetEncoder<N> {
+    type SystemData = (
+        Write<'a, EncodingBuffer>,
+        Read<'a, AssetStorage<SpriteSheet>>,
+        Read<'a, AssetStorage<Texture>>,
+        ReadStorage<'a, SpriteRender>,
+        ReadStorage<'a, Transform<N>>,
+        ReadStorage<'a, Flipped>,
+        ReadStorage<'a, Rgba>,
+        ReadStorage<'a, Transparent>,
+        ReadStorage<'a, Hidden>,
+        ReadStorage<'a, HiddenPropagate>,
+    );
+
+    fn run(
+        &mut self,
+        (
+            mut buffer,
+            sprite_sheet_storage,
+            tex_storage,
+            sprite_render,
+            transform,
+            flipped,
+            rgba,
+            transparent,
+            hidden,
+            hidden_prop,
+        ): Self::SystemData,
+    ) {
+        for (sprite_render, transform, flipped, rgba, transparent, _, _) in (
+            &sprite_render,
+            &transform,
+            flipped.maybe(),
+            rgba.maybe(),
+            transparent.maybe(),
+            !&hidden,
+            !&hidden_prop,
+        )
+            .join()
+        {
+            if let Some(data) = encode_sprite(
+                sprite_render,
+                transform,
+                flipped,
+                rgba,
+                transparent,
+                &sprite_sheet_storage,
+                &tex_storage,
+            ) {
+                buffer.data.push(data);
+            }
+        }
+    }
+}
+
+/// Encodes bare `TextureHandle` entities (entities drawn as a blit of their
+/// whole texture, with no `MeshHandle`) into the shared `EncodingBuffer`.
+///
+/// See `SpriteFlat2DAssetEncoder` for why this runs as a `System` rather than
+/// inline in `DrawFlat2DEncoded::apply`.
+#[derive(Derivative)]
+#[derivative(Default(bound = ""))]
+pub struct ImageFlat2DAssetEncoder<N> {
+    _pd: PhantomData<N>,
+}
+
+impl<'a, N: Real> System<'a> for ImageFlat2DAssetEncoder<N> {
+    type SystemData = (
+        Write<'a, EncodingBuffer>,
+        Read<'a, AssetStorage<Texture>>,
+        ReadStorage<'a, TextureHandle>,
+        ReadStorage<'a, Transform<N>>,
+        ReadStorage<'a, Flipped>,
+        ReadStorage<'a, Rgba>,
+        ReadStorage<'a, Transparent>,
+        ReadStorage<'a, Hidden>,
+        ReadStorage<'a, HiddenPropagate>,
+        ReadStorage<'a, MeshHandle>,
+    );
+
+    fn run(
+        &mut self,
+        (
+            mut buffer,
+            tex_storage,
+            texture_handle,
+            transform,
+            flipped,
+            rgba,
+            transparent,
+            hidden,
+            hidden_prop,
+            mesh,
+        ): Self::SystemData,
+    ) {
+        for (texture_handle, transform, flipped, rgba, transparent, _, _, _) in (
+            &texture_handle,
+            &transform,
+            flipped.maybe(),
+            rgba.maybe(),
+            transparent.maybe(),
+            !&hidden,
+            !&hidden_prop,
+            !&mesh,
+        )
+            .join()
+        {
+            if let Some(data) =
+                encode_image(texture_handle, transform, flipped, rgba, transparent, &tex_storage)
+            {
+                buffer.data.push(data);
+            }
+        }
+    }
+}
+
+/// Like `DrawFlat2D`, but draws pre-encoded `Flat2DData` drained from the
+/// `EncodingBuffer` resource instead of computing instance data itself.
+///
+/// Pair this with `SpriteFlat2DAssetEncoder`/`ImageFlat2DAssetEncoder` in the
+/// dispatcher: they do the asset lookups and matrix math in parallel during the
+/// normal dispatch, so `apply` only has to sort and draw.
+///
+/// # Type Parameters:
+///
+/// * `N`: `RealBound` (f32, f64)
+#[derive(Derivative, Clone, Debug)]
+#[derivative(Default(bound = "Self: Pass"))]
+pub struct DrawFlat2DEncoded<N>
+where
+    N: Real,
+{
+    #[derivative(Default(value = "default_transparency()"))]
+    transparency: Option<(ColorMask, Blend, Option<DepthMode>)>,
+    _pd: PhantomData<N>,
+}
+
+impl<N> DrawFlat2DEncoded<N>
+where
+    Self: Pass,
+    N: Real,
+{
+    /// Create instance of `DrawFlat2DEncoded` pass
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Transparency is enabled by default.
+    /// If you pass false to this function transparency will be disabled.
+    ///
+    /// If you pass true and this was disabled previously default settings will be reinstated.
+    /// If you pass true and this was already enabled this will do nothing.
+    pub fn with_transparency(mut self, input: bool) -> Self {
+        if input {
+            if self.transparency.is_none() {
+                self.transparency = default_transparency();
+            }
+        } else {
+            self.transparency = None;
+        }
+        self
+    }
+
+    fn attributes() -> Attributes<'static> {
+        DrawFlat2D::<N>::attributes()
+    }
+}
+
+impl<'a, N: Real> PassData<'a> for DrawFlat2DEncoded<N> {
+    type Data = (
+        Read<'a, ActiveCamera>,
+        ReadStorage<'a, Camera>,
+        ReadStorage<'a, Transform<N>>,
+        Read<'a, AssetStorage<Texture>>,
+        Write<'a, EncodingBuffer>,
+    );
+}
+
+impl<N: Real> Pass for DrawFlat2DEncoded<N> {
+    fn compile(&mut self, effect: NewEffect<'_>) -> Result<Effect, Error> {
+        use std::mem;
+
+        let mut builder = effect.simple(VERT_SRC, FRAG_SRC);
+        builder
+            .without_back_face_culling()
+            .with_raw_constant_buffer(
+                "ViewArgs",
+                mem::size_of::<<ViewArgs as Uniform>::Std140>(),
+                1,
+            )
+            .with_raw_vertex_buffer(Self::attributes(), SpriteInstance::size() as ElemStride, 1);
+        setup_textures(&mut builder, &TEXTURES);
+        match self.transparency {
+            Some((mask, blend, depth)) => builder.with_blended_output("color", mask, blend, depth),
+            None => builder.with_output("color", Some(DepthMode::LessEqualWrite)),
+        };
+        builder.build()
+    }
+
+    fn apply<'a, 'b: 'a>(
+        &'a mut self,
+        encoder: &mut Encoder,
+        effect: &mut Effect,
+        mut factory: Factory,
+        (active, camera, transform, tex_storage, mut buffer): <Self as PassData<'a>>::Data,
+    ) {
+        use gfx::{
+            buffer,
+            memory::{Bind, Typed},
+            Factory,
+        };
+
+        if buffer.data.is_empty() {
+            return;
+        }
+
+        let camera = get_camera(active, &camera, &transform);
+
+        // Drained and sorted; the sprite sheet lookups and instance matrix math
+        // were done by the `*Flat2DAssetEncoder` systems. What is left here is
+        // the view-space depth of each transparent instance for the sort, and
+        // one `AssetStorage<Texture>` lookup per instance to bind its texture
+        // when its run is flushed. Opaque instances
+        // are grouped by texture id for batching, same as `TextureBatch::sort`;
+        // transparent ones are sorted back-to-front by view-space depth instead,
+        // since blending is order-dependent, and placed after the opaque run so
+        // they draw (and blend) on top of it.
+        let (mut opaque, mut transparent): (Vec<Flat2DData>, Vec<Flat2DData>) =
+            buffer.data.drain(..).partition(|data| !data.transparent);
+
+        opaque.sort_by(|a, b| a.texture_handle.id().cmp(&b.texture_handle.id()));
+
+        let view = camera
+            .map(|(_, camera_transform)| {
+                camera_transform
+                    .global_matrix()
+                    .try_inverse()
+                    .unwrap_or_else(Matrix4::identity)
+            })
+            .unwrap_or_else(Matrix4::identity);
+
+        transparent.sort_by(|a, b| {
+            encoded_view_space_depth(b, &view)
+                .partial_cmp(&encoded_view_space_depth(a, &view))
+                .unwrap_or(std::cmp::Ordering::Equal)
+                .then_with(|| a.texture_handle.id().cmp(&b.texture_handle.id()))
+        });
+
+        let mut instances = opaque;
+        instances.extend(transparent);
+
+        set_view_args(effect, encoder, camera);
+
+        let mut instance_data = Vec::<f32>::new();
+        let mut num_instances = 0;
+        let num_instances_total = instances.len();
+
+        for (i, instance) in instances.iter().enumerate() {
+            let texture = tex_storage
+                .get(&instance.texture_handle)
+                .expect("Unable to get texture of sprite");
+
+            instance_data.extend(&[
+                instance.dir_x[0],
+                instance.dir_x[1],
+                instance.dir_y[0],
+                instance.dir_y[1],
+                instance.pos[0],
+                instance.pos[1],
+                instance.uv[0],
+                instance.uv[1],
+                instance.uv[2],
+                instance.uv[3],
+                instance.depth,
+                instance.rgba[0],
+                instance.rgba[1],
+                instance.rgba[2],
+                instance.rgba[3],
+            ]);
+            num_instances += 1;
+
+            let need_flush = i >= num_instances_total - 1
+                || instances[i + 1].texture_handle.id() != instance.texture_handle.id();
+
+            if need_flush {
+                add_texture(effect, texture);
+
+                let vbuf = factory
+                    .create_buffer_immutable(&instance_data, buffer::Role::Vertex, Bind::empty())
+                    .expect("Unable to create immutable buffer for `DrawFlat2DEncoded`");
+
+                for _ in Self::attributes() {
+                    effect.data.vertex_bufs.push(vbuf.raw().clone());
+                }
+
+                effect.draw(
+                    &Slice {
+                        start: 0,
+                        end: 6,
+                        base_vertex: 0,
+                        instances: Some((num_instances, 0)),
+                        buffer: Default::default(),
+                    },
+                    encoder,
+                );
+
+                effect.clear();
+
+                num_instances = 0;
+                instance_data.clear();
+            }
+        }
+    }
+}
+
+/// Per-sprite blend mode, read from `PassData` so individual sprites can
+/// composite differently than the whole pass's `transparency` setting ---
+/// useful for particles, glows, and light overlays.
+///
+/// Entities without this component fall back to the pass-wide `transparency`
+/// setting, matching `DrawFlat2D`'s behavior before this component existed.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum BlendMode {
+    /// No blending; renders fully opaque.
+    Normal,
+    /// Standard alpha blending; this is `DrawFlat2D`'s default transparency setting.
+    Alpha,
+    /// Additive blending: `(One, One)`. Good for glows and particles.
+    Add,
+    /// Multiplicative blending: `(Zero, SrcColor)`. Good for shadows and tinting.
+    Multiply,
+    /// Screen blending: `(One, InvSrcColor)`. Good for light overlays.
+    Screen,
+}
+
+impl Component for BlendMode {
+    type Storage = DenseVecStorage<Self>;
+}
+
+impl BlendMode {
+    /// The fixed-function `Blend` preset backing this mode, as used by a
+    /// precompiled `Effect` in `DrawFlat2D::blend_effects`. `Normal` and
+    /// `Alpha` return the default transparency blend, but no effect is built
+    /// from it: `Normal`'s pipeline is built with `with_output` (no blending
+    /// at all), and `Alpha` reuses the effect compiled from the pass's own
+    /// `transparency` setting.
+    fn blend(self) -> Blend {
+        match self {
+            BlendMode::Normal | BlendMode::Alpha => {
+                default_transparency().expect("default_transparency always returns Some").1
+            }
+            BlendMode::Add => Blend {
+                color: BlendChannel {
+                    equation: Equation::Add,
+                    source: Factor::One,
+                    destination: Factor::One,
+                },
+                alpha: BlendChannel {
+                    equation: Equation::Add,
+                    source: Factor::One,
+                    destination: Factor::One,
+                },
+            },
+            BlendMode::Multiply => Blend {
+                color: BlendChannel {
+                    equation: Equation::Add,
+                    source: Factor::Zero,
+                    destination: Factor::ZeroPlus(BlendValue::SourceColor),
+                },
+                alpha: BlendChannel {
+                    equation: Equation::Add,
+                    source: Factor::Zero,
+                    destination: Factor::ZeroPlus(BlendValue::SourceColor),
+                },
+            },
+            BlendMode::Screen => Blend {
+                color: BlendChannel {
+                    equation: Equation::Add,
+                    source: Factor::One,
+                    destination: Factor::OneMinus(BlendValue::SourceColor),
+                },
+                alpha: BlendChannel {
+                    equation: Equation::Add,
+                    source: Factor::One,
+                    destination: Factor::OneMinus(BlendValue::SourceColor),
+                },
+            },
+        }
+    }
+}
+
+/// Selects which conversion matrix `FRAG_SRC_YUV` uses to turn a `YuvSprite`'s
+/// planes into RGB, bound via the shader's `yuv_matrix` uniform. The
+/// discriminants match the branch the shader tests against.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum YuvColorSpace {
+    /// ITU-R BT.601, used by standard-definition video.
+    Bt601 = 0,
+    /// ITU-R BT.709, used by HD video.
+    Bt709 = 1,
+}
+
+/// Draws a decoded video frame as three separate Y/U/V texture planes instead
+/// of a single RGB texture, letting `DrawFlat2D`'s YUV fragment shader do the
+/// color space conversion instead of a CPU-side conversion pass.
+#[derive(Clone, Debug)]
+pub struct YuvSprite {
+    /// Luma (`Y`) plane.
+    pub y_plane: Handle<Texture>,
+    /// Blue-difference chroma (`U`) plane.
+    pub u_plane: Handle<Texture>,
+    /// Red-difference chroma (`V`) plane.
+    pub v_plane: Handle<Texture>,
+    /// Width of the decoded frame, in pixels.
+    pub width: usize,
+    /// Height of the decoded frame, in pixels.
+    pub height: usize,
+    /// Conversion matrix to decode this frame's planes with.
+    pub colorspace: YuvColorSpace,
+}
+
+impl Component for YuvSprite {
+    type Storage = DenseVecStorage<Self>;
+}
+
+/// Nine-slice scaling for a plain `TextureHandle` blit: border insets (in
+/// texels, measured against the bound texture) and a target output size, so
+/// a single texture can be stretched into a panel without distorting its
+/// corners --- the standard technique for scalable UI frames and buttons.
+///
+/// `TextureBatch::encode` expands a quad carrying this component into nine
+/// instances sharing the same texture/batch: the four corners keep their
+/// native texel size, the four edges stretch along one axis, and the center
+/// fills the remainder. Only pairs with plain image quads (`TextureHandle`),
+/// not `SpriteRender` or `YuvSprite`, since the insets are measured against
+/// the whole bound texture rather than a sub-region of a sprite sheet. Not
+/// combined with `Flipped` in this implementation.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct NineSlice {
+    /// Texels to keep unstretched from the left edge.
+    pub left: u32,
+    /// Texels to keep unstretched from the right edge.
+    pub right: u32,
+    /// Texels to keep unstretched from the top edge.
+    pub top: u32,
+    /// Texels to keep unstretched from the bottom edge.
+    pub bottom: u32,
+    /// Target width of the scaled panel.
+    pub width: f32,
+    /// Target height of the scaled panel.
+    pub height: f32,
+}
+
+impl Component for NineSlice {
+    type Storage = DenseVecStorage<Self>;
+}
+
 #[derive(Clone, Debug)]
 enum TextureDrawData<N: Real> {
     Sprite {
@@ -284,6 +1171,8 @@ enum TextureDrawData<N: Real> {
         render: SpriteRender,
         flipped: Option<Flipped>,
         rgba: Option<Rgba>,
+        transparent: bool,
+        blend_mode: BlendMode,
         transform: Transform<N>,
     },
     Image {
@@ -291,23 +1180,54 @@ enum TextureDrawData<N: Real> {
         transform: Transform<N>,
         flipped: Option<Flipped>,
         rgba: Option<Rgba>,
+        transparent: bool,
+        blend_mode: BlendMode,
+        width: usize,
+        height: usize,
+        nine_slice: Option<NineSlice>,
+    },
+    Yuv {
+        y_plane: Handle<Texture>,
+        u_plane: Handle<Texture>,
+        v_plane: Handle<Texture>,
+        transform: Transform<N>,
+        flipped: Option<Flipped>,
+        rgba: Option<Rgba>,
+        transparent: bool,
+        blend_mode: BlendMode,
         width: usize,
         height: usize,
+        colorspace: YuvColorSpace,
     },
 }
 
 impl<N: Real> TextureDrawData<N> {
+    /// The texture batches are grouped and flushed by. For `Yuv` this is the
+    /// `Y` plane; `plane_ids` additionally guards against the `U`/`V` planes
+    /// changing while the `Y` plane coincidentally stays the same.
     pub fn texture_handle(&self) -> &Handle<Texture> {
         match self {
             TextureDrawData::Sprite { texture_handle, .. } => texture_handle,
             TextureDrawData::Image { texture_handle, .. } => texture_handle,
+            TextureDrawData::Yuv { y_plane, .. } => y_plane,
         }
     }
 
     pub fn tex_id(&self) -> u32 {
+        self.texture_handle().id()
+    }
+
+    /// `Some((y, u, v))` plane ids for a `Yuv` quad, `None` otherwise. Used to
+    /// flush the batch when the plane bindings change between two `Yuv` quads.
+    pub fn plane_ids(&self) -> Option<(u32, u32, u32)> {
         match self {
-            TextureDrawData::Sprite { texture_handle, .. } => texture_handle.id(),
-            TextureDrawData::Image { texture_handle, .. } => texture_handle.id(),
+            TextureDrawData::Yuv {
+                y_plane,
+                u_plane,
+                v_plane,
+                ..
+            } => Some((y_plane.id(), u_plane.id(), v_plane.id())),
+            _ => None,
         }
     }
 
@@ -315,6 +1235,149 @@ impl<N: Real> TextureDrawData<N> {
         match self {
             TextureDrawData::Sprite { flipped, .. } => flipped,
             TextureDrawData::Image { flipped, .. } => flipped,
+            TextureDrawData::Yuv { flipped, .. } => flipped,
+        }
+    }
+
+    pub fn blend_mode(&self) -> BlendMode {
+        match self {
+            TextureDrawData::Sprite { blend_mode, .. } => *blend_mode,
+            TextureDrawData::Image { blend_mode, .. } => *blend_mode,
+            TextureDrawData::Yuv { blend_mode, .. } => *blend_mode,
+        }
+    }
+
+    pub fn transform(&self) -> &Transform<N> {
+        match self {
+            TextureDrawData::Sprite { transform, .. } => transform,
+            TextureDrawData::Image { transform, .. } => transform,
+            TextureDrawData::Yuv { transform, .. } => transform,
+        }
+    }
+
+    /// Whether this quad is tagged `Transparent` or its resolved `rgba` has
+    /// any transparency. Used by `TextureBatch::sort` to route it into the
+    /// back-to-front sorted sub-list instead of the tex-id-sorted opaque one.
+    pub fn is_transparent(&self) -> bool {
+        match self {
+            TextureDrawData::Sprite { transparent, .. } => *transparent,
+            TextureDrawData::Image { transparent, .. } => *transparent,
+            TextureDrawData::Yuv { transparent, .. } => *transparent,
+        }
+    }
+}
+
+/// View-space depth of `quad`'s translation, used by `TextureBatch::sort` to
+/// order transparent quads back-to-front. `view` is the camera transform's
+/// inverted `global_matrix`. The camera looks down `-Z`, so the depth is the
+/// negated view-space `z`: larger values are farther from the camera.
+fn view_space_depth<N: Real>(quad: &TextureDrawData<N>, view: &Matrix4<N>) -> f32 {
+    let world_pos = quad.transform().global_matrix().column(3).into_owned();
+    let view_pos = view * world_pos;
+    convert(-view_pos.z)
+}
+
+/// View-space depth of `data`'s entity translation, used by
+/// `DrawFlat2DEncoded::apply` to order transparent quads back-to-front the same
+/// way `view_space_depth` does for `TextureBatch`. Uses `data.translation`
+/// (the entity's `global_matrix().column(3)`), not the offset `pos`/`depth`
+/// used for the instance quad, so both passes agree on draw order for the
+/// same entity.
+fn encoded_view_space_depth<N: Real>(data: &Flat2DData, view: &Matrix4<N>) -> f32 {
+    let world_pos = Vector4::<N>::new(
+        convert(data.translation[0]),
+        convert(data.translation[1]),
+        convert(data.translation[2]),
+        one(),
+    );
+    let view_pos = view * world_pos;
+    convert(-view_pos.z)
+}
+
+/// Expands a nine-sliced `Image` quad into nine instances (corners, edges,
+/// center), appending them to `instance_data` and bumping `num_instances`
+/// accordingly. The four corners keep their native texel size, the four
+/// edges stretch along one axis, and the center fills the remainder, with
+/// UVs subdivided by the same insets measured against the source texture.
+///
+/// Not combined with `Flipped`; all nine cells use the texture's native
+/// (unflipped) UV orientation.
+fn encode_nine_slice<N: Real>(
+    transform: &Transform<N>,
+    width: usize,
+    height: usize,
+    rgba: Option<Rgba>,
+    nine_slice: &NineSlice,
+    instance_data: &mut Vec<f32>,
+    num_instances: &mut u32,
+) {
+    let global_matrix = &transform.global_matrix();
+    let pos = global_matrix * Vector4::<N>::new(one(), one(), zero(), one());
+    let rgba = rgba.unwrap_or(Rgba::WHITE);
+
+    let tex_width = width as f32;
+    let tex_height = height as f32;
+    let left = nine_slice.left as f32;
+    let right = nine_slice.right as f32;
+    let top = nine_slice.top as f32;
+    let bottom = nine_slice.bottom as f32;
+
+    // World-space size of each column/row: corners keep their native texel
+    // size, the middle one stretches to fill whatever the target size leaves
+    // after the corners/edges are accounted for.
+    let col_size = [left, (nine_slice.width - left - right).max(0.0), right];
+    let row_size = [bottom, (nine_slice.height - top - bottom).max(0.0), top];
+
+    let col_dir = [
+        global_matrix.column(0) * col_size[0],
+        global_matrix.column(0) * col_size[1],
+        global_matrix.column(0) * col_size[2],
+    ];
+    let row_dir = [
+        global_matrix.column(1) * row_size[0],
+        global_matrix.column(1) * row_size[1],
+        global_matrix.column(1) * row_size[2],
+    ];
+
+    let col_offset = [
+        Vector4::<N>::zeros(),
+        col_dir[0],
+        col_dir[0] + col_dir[1],
+    ];
+    let row_offset = [
+        Vector4::<N>::zeros(),
+        row_dir[0],
+        row_dir[0] + row_dir[1],
+    ];
+
+    // UV fractions of the source texture at each column/row boundary.
+    let u = [0.0, left / tex_width, 1.0 - right / tex_width, 1.0];
+    let v = [0.0, bottom / tex_height, 1.0 - top / tex_height, 1.0];
+
+    for row in 0..3 {
+        for col in 0..3 {
+            let cell_pos = pos + col_offset[col] + row_offset[row];
+            let dir_x = col_dir[col];
+            let dir_y = row_dir[row];
+
+            instance_data.extend(&[
+                dir_x.x,
+                dir_x.y,
+                dir_y.x,
+                dir_y.y,
+                cell_pos.x,
+                cell_pos.y,
+                u[col],
+                u[col + 1],
+                v[row],
+                v[row + 1],
+                cell_pos.z,
+                rgba.0,
+                rgba.1,
+                rgba.2,
+                rgba.3,
+            ]);
+            *num_instances += 1;
         }
     }
 }
@@ -322,6 +1385,13 @@ impl<N: Real> TextureDrawData<N> {
 #[derive(Clone, Default, Debug)]
 struct TextureBatch<N: Real> {
     textures: Vec<TextureDrawData<N>>,
+    /// Persistent dynamic vertex buffer reused across flushes (and frames),
+    /// resized only when a run needs more capacity than it currently has.
+    /// Avoids the `factory.create_buffer_immutable` churn of allocating a
+    /// fresh GPU buffer on every flush.
+    vbuf: Option<Buffer<Resources, f32>>,
+    /// Capacity of `vbuf`, in `f32` elements.
+    vbuf_capacity: usize,
 }
 
 impl<N: Real> TextureBatch<N> {
@@ -331,6 +1401,9 @@ impl<N: Real> TextureBatch<N> {
         transform: Option<&Transform<N>>,
         flipped: Option<&Flipped>,
         rgba: Option<&Rgba>,
+        transparent: Option<&Transparent>,
+        blend_mode: Option<&BlendMode>,
+        nine_slice: Option<&NineSlice>,
         tex_storage: &AssetStorage<Texture>,
     ) {
         let transform = match transform {
@@ -350,9 +1423,49 @@ impl<N: Real> TextureBatch<N> {
             texture_handle: texture_handle.clone(),
             transform: *transform,
             flipped: flipped.cloned(),
+            transparent: transparent.is_some() || rgba.map_or(false, |rgba| rgba.3 < 1.0),
             rgba: rgba.cloned(),
+            blend_mode: blend_mode.cloned().unwrap_or(BlendMode::Alpha),
             width: texture_dims.0,
             height: texture_dims.1,
+            nine_slice: nine_slice.cloned(),
+        });
+    }
+
+    pub fn add_yuv(
+        &mut self,
+        yuv_sprite: &YuvSprite,
+        transform: Option<&Transform<N>>,
+        flipped: Option<&Flipped>,
+        rgba: Option<&Rgba>,
+        transparent: Option<&Transparent>,
+        blend_mode: Option<&BlendMode>,
+        tex_storage: &AssetStorage<Texture>,
+    ) {
+        let transform = match transform {
+            Some(v) => v,
+            None => return,
+        };
+
+        for plane in &[&yuv_sprite.y_plane, &yuv_sprite.u_plane, &yuv_sprite.v_plane] {
+            if tex_storage.get(plane).is_none() {
+                warn!("Texture not loaded for YUV plane: `{:?}`.", plane);
+                return;
+            }
+        }
+
+        self.textures.push(TextureDrawData::Yuv {
+            y_plane: yuv_sprite.y_plane.clone(),
+            u_plane: yuv_sprite.u_plane.clone(),
+            v_plane: yuv_sprite.v_plane.clone(),
+            transform: *transform,
+            flipped: flipped.cloned(),
+            transparent: transparent.is_some() || rgba.map_or(false, |rgba| rgba.3 < 1.0),
+            rgba: rgba.cloned(),
+            blend_mode: blend_mode.cloned().unwrap_or(BlendMode::Alpha),
+            width: yuv_sprite.width,
+            height: yuv_sprite.height,
+            colorspace: yuv_sprite.colorspace,
         });
     }
 
@@ -362,6 +1475,8 @@ impl<N: Real> TextureBatch<N> {
         transform: Option<&Transform<N>>,
         flipped: Option<&Flipped>,
         rgba: Option<&Rgba>,
+        transparent: Option<&Transparent>,
+        blend_mode: Option<&BlendMode>,
         sprite_sheet_storage: &AssetStorage<SpriteSheet>,
         tex_storage: &AssetStorage<Texture>,
     ) {
@@ -395,29 +1510,65 @@ impl<N: Real> TextureBatch<N> {
             texture_handle,
             render: sprite_render.clone(),
             flipped: flipped.cloned(),
+            transparent: transparent.is_some() || rgba.map_or(false, |rgba| rgba.3 < 1.0),
             rgba: rgba.cloned(),
+            blend_mode: blend_mode.cloned().unwrap_or(BlendMode::Alpha),
             transform: *transform,
         });
     }
 
-    /// Optimize the sprite order to generating more coherent batches.
-    pub fn sort(&mut self) {
-        // Only takes the texture into account for now.
-        self.textures.sort_by(|a, b| a.tex_id().cmp(&b.tex_id()));
+    /// Order the batch for correct, coherent drawing.
+    ///
+    /// Opaque quads are sorted by `(blend_mode, tex_id)` so draws that can
+    /// share both the blend state and the texture binding stay contiguous;
+    /// draw order among them doesn't affect the result. Transparent quads
+    /// (tagged `Transparent`, or `rgba.3 < 1.0`) are sorted separately, back-to-front by view-space
+    /// depth, since blending is order-dependent; ties break by `tex_id` to
+    /// keep some batching. Opaque quads are placed first so they draw (and
+    /// depth-write) before any blending happens.
+    pub fn sort(&mut self, camera: Option<(&Camera, &Transform<N>)>) {
+        let (mut opaque, mut transparent): (Vec<_>, Vec<_>) = self
+            .textures
+            .drain(..)
+            .partition(|quad| !quad.is_transparent());
+
+        opaque.sort_by_key(|data| (data.blend_mode(), data.tex_id()));
+
+        let view = camera
+            .map(|(_, camera_transform)| {
+                camera_transform
+                    .global_matrix()
+                    .try_inverse()
+                    .unwrap_or_else(Matrix4::identity)
+            })
+            .unwrap_or_else(Matrix4::identity);
+
+        transparent.sort_by(|a, b| {
+            view_space_depth(b, &view)
+                .partial_cmp(&view_space_depth(a, &view))
+                .unwrap_or(std::cmp::Ordering::Equal)
+                .then_with(|| a.tex_id().cmp(&b.tex_id()))
+        });
+
+        self.textures = opaque;
+        self.textures.extend(transparent);
     }
 
     pub fn encode(
-        &self,
+        &mut self,
         encoder: &mut Encoder,
         factory: &mut Factory,
         effect: &mut Effect,
+        blend_effects: &mut HashMap<BlendMode, Effect>,
+        mut yuv_effect: Option<&mut Effect>,
+        yuv_blend_effects: &mut HashMap<BlendMode, Effect>,
         camera: Option<(&Camera, &Transform<N>)>,
         sprite_sheet_storage: &AssetStorage<SpriteSheet>,
         tex_storage: &AssetStorage<Texture>,
     ) {
         use gfx::{
             buffer,
-            memory::{Bind, Typed},
+            memory::{Bind, Typed, Usage},
             Factory,
         };
 
@@ -425,9 +1576,6 @@ impl<N: Real> TextureBatch<N> {
             return;
         }
 
-        // Sprite vertex shader
-        set_view_args(effect, encoder, camera);
-
         // We might be able to improve performance here if we
         // preallocate the maximum needed capacity. We need to
         // iterate over the sprites though to find out the longest
@@ -450,107 +1598,202 @@ impl<N: Real> TextureBatch<N> {
                 _ => (false, false),
             };
 
-            let (dir_x, dir_y, pos, uv_left, uv_right, uv_top, uv_bottom, rgba) = match quad {
-                TextureDrawData::Sprite {
-                    render,
+            if let TextureDrawData::Image {
+                transform,
+                rgba,
+                width,
+                height,
+                nine_slice: Some(nine_slice),
+                ..
+            } = quad
+            {
+                encode_nine_slice(
                     transform,
-                    rgba,
-                    ..
-                } => {
-                    let sprite_sheet = sprite_sheet_storage
-                        .get(&render.sprite_sheet)
-                        .expect(
-                            "Unreachable: Existence of sprite sheet checked when collecting the sprites",
-                        );
+                    *width,
+                    *height,
+                    *rgba,
+                    nine_slice,
+                    &mut instance_data,
+                    &mut num_instances,
+                );
+            } else {
+                let (dir_x, dir_y, pos, uv_left, uv_right, uv_top, uv_bottom, rgba) = match quad {
+                    TextureDrawData::Sprite {
+                        render,
+                        transform,
+                        rgba,
+                        ..
+                    } => {
+                        let sprite_sheet = sprite_sheet_storage
+                            .get(&render.sprite_sheet)
+                            .expect(
+                                "Unreachable: Existence of sprite sheet checked when collecting the sprites",
+                            );
 
-                    // Append sprite to instance data.
-                    let sprite_data = &sprite_sheet.sprites[render.sprite_number];
-
-                    let tex_coords = &sprite_data.tex_coords;
-                    let (uv_left, uv_right) = if flip_horizontal {
-                        (tex_coords.right, tex_coords.left)
-                    } else {
-                        (tex_coords.left, tex_coords.right)
-                    };
-                    let (uv_bottom, uv_top) = if flip_vertical {
-                        (tex_coords.top, tex_coords.bottom)
-                    } else {
-                        (tex_coords.bottom, tex_coords.top)
-                    };
-
-                    let global_matrix = &transform.global_matrix();
-
-                    let dir_x = global_matrix.column(0) * sprite_data.width;
-                    let dir_y = global_matrix.column(1) * sprite_data.height;
-
-                    // The offsets are negated to shift the sprite left and down relative to the entity, in
-                    // regards to pivot points. This is the convention adopted in:
-                    //
-                    // * libgdx: <https://gamedev.stackexchange.com/q/22553>
-                    // * godot: <https://godotengine.org/qa/9784>
-                    let pos = global_matrix
-                        * Vector4::new(-sprite_data.offsets[0], -sprite_data.offsets[1], 0.0, 1.0);
-
-                    (
-                        dir_x, dir_y, pos, uv_left, uv_right, uv_top, uv_bottom, rgba,
-                    )
-                }
-                TextureDrawData::Image {
-                    transform,
-                    width,
-                    height,
-                    rgba,
-                    ..
-                } => {
-                    let (uv_left, uv_right) = if flip_horizontal {
-                        (1.0, 0.0)
-                    } else {
-                        (0.0, 1.0)
-                    };
-                    let (uv_bottom, uv_top) = if flip_vertical {
-                        (1.0, 0.0)
-                    } else {
-                        (0.0, 1.0)
-                    };
-
-                    let global_matrix = &transform.global_matrix();
-
-                    let dir_x = global_matrix.column(0) * (*width as f32);
-                    let dir_y = global_matrix.column(1) * (*height as f32);
-
-                    let pos = global_matrix * Vector4::<N>::new(one(), one(), zero(), one());
-
-                    (
-                        dir_x, dir_y, pos, uv_left, uv_right, uv_top, uv_bottom, rgba,
-                    )
-                }
-            };
-            let rgba = rgba.unwrap_or(Rgba::WHITE);
-            instance_data.extend(&[
-                dir_x.x, dir_x.y, dir_y.x, dir_y.y, pos.x, pos.y, uv_left, uv_right, uv_bottom,
-                uv_top, pos.z, rgba.0, rgba.1, rgba.2, rgba.3,
-            ]);
-            num_instances += 1;
+                        // Append sprite to instance data.
+                        let sprite_data = &sprite_sheet.sprites[render.sprite_number];
+
+                        let tex_coords = &sprite_data.tex_coords;
+                        let (uv_left, uv_right) = if flip_horizontal {
+                            (tex_coords.right, tex_coords.left)
+                        } else {
+                            (tex_coords.left, tex_coords.right)
+                        };
+                        let (uv_bottom, uv_top) = if flip_vertical {
+                            (tex_coords.top, tex_coords.bottom)
+                        } else {
+                            (tex_coords.bottom, tex_coords.top)
+                        };
+
+                        let global_matrix = &transform.global_matrix();
+
+                        let dir_x = global_matrix.column(0) * sprite_data.width;
+                        let dir_y = global_matrix.column(1) * sprite_data.height;
+
+                        // The offsets are negated to shift the sprite left and down relative to the entity, in
+                        // regards to pivot points. This is the convention adopted in:
+                        //
+                        // * libgdx: <https://gamedev.stackexchange.com/q/22553>
+                        // * godot: <https://godotengine.org/qa/9784>
+                        let pos = global_matrix
+                            * Vector4::new(-sprite_data.offsets[0], -sprite_data.offsets[1], 0.0, 1.0);
+
+                        (
+                            dir_x, dir_y, pos, uv_left, uv_right, uv_top, uv_bottom, rgba,
+                        )
+                    }
+                    TextureDrawData::Image {
+                        transform,
+                        width,
+                        height,
+                        rgba,
+                        ..
+                    }
+                    | TextureDrawData::Yuv {
+                        transform,
+                        width,
+                        height,
+                        rgba,
+                        ..
+                    } => {
+                        let (uv_left, uv_right) = if flip_horizontal {
+                            (1.0, 0.0)
+                        } else {
+                            (0.0, 1.0)
+                        };
+                        let (uv_bottom, uv_top) = if flip_vertical {
+                            (1.0, 0.0)
+                        } else {
+                            (0.0, 1.0)
+                        };
+
+                        let global_matrix = &transform.global_matrix();
+
+                        let dir_x = global_matrix.column(0) * (*width as f32);
+                        let dir_y = global_matrix.column(1) * (*height as f32);
 
-            // Need to flush outstanding draw calls due to state switch (texture).
+                        let pos = global_matrix * Vector4::<N>::new(one(), one(), zero(), one());
+
+                        (
+                            dir_x, dir_y, pos, uv_left, uv_right, uv_top, uv_bottom, rgba,
+                        )
+                    }
+                };
+                let rgba = rgba.unwrap_or(Rgba::WHITE);
+                instance_data.extend(&[
+                    dir_x.x, dir_x.y, dir_y.x, dir_y.y, pos.x, pos.y, uv_left, uv_right,
+                    uv_bottom, uv_top, pos.z, rgba.0, rgba.1, rgba.2, rgba.3,
+                ]);
+                num_instances += 1;
+            }
+
+            // Need to flush outstanding draw calls due to a state switch: either the
+            // texture changes, or the blend mode changes (which also means the
+            // pipeline we need to draw with changes).
             //
             // 1. We are at the last sprite and want to submit all pending work.
             // 2. The next sprite will use a different texture triggering a flush.
+            // 3. The next sprite uses a different blend mode triggering a flush.
+            // 4. The next sprite is a YUV quad with different plane bindings.
             let need_flush = i >= num_quads - 1
-                || self.textures[i + 1].texture_handle().id() != quad.texture_handle().id();
+                || self.textures[i + 1].texture_handle().id() != quad.texture_handle().id()
+                || self.textures[i + 1].blend_mode() != quad.blend_mode()
+                || self.textures[i + 1].plane_ids() != quad.plane_ids();
 
             if need_flush {
-                add_texture(effect, texture);
+                let blend_mode = quad.blend_mode();
+                let active_effect = if quad.plane_ids().is_some() {
+                    match blend_mode {
+                        BlendMode::Alpha => &mut **yuv_effect
+                            .as_mut()
+                            .expect("YUV effect was not precompiled"),
+                        _ => yuv_blend_effects
+                            .get_mut(&blend_mode)
+                            .expect("YUV blend mode effect was not precompiled"),
+                    }
+                } else {
+                    match blend_mode {
+                        BlendMode::Alpha => &mut *effect,
+                        _ => blend_effects
+                            .get_mut(&blend_mode)
+                            .expect("Blend mode effect was not precompiled"),
+                    }
+                };
 
-                let vbuf = factory
-                    .create_buffer_immutable(&instance_data, buffer::Role::Vertex, Bind::empty())
-                    .expect("Unable to create immutable buffer for `TextureBatch`");
+                set_view_args(active_effect, encoder, camera);
+                match quad {
+                    TextureDrawData::Yuv {
+                        y_plane,
+                        u_plane,
+                        v_plane,
+                        colorspace,
+                        ..
+                    } => {
+                        add_texture(
+                            active_effect,
+                            tex_storage.get(y_plane).expect("Unable to get Y plane"),
+                        );
+                        add_texture(
+                            active_effect,
+                            tex_storage.get(u_plane).expect("Unable to get U plane"),
+                        );
+                        add_texture(
+                            active_effect,
+                            tex_storage.get(v_plane).expect("Unable to get V plane"),
+                        );
+                        active_effect.update_global("yuv_matrix", *colorspace as i32);
+                    }
+                    _ => add_texture(active_effect, texture),
+                }
+
+                // Grow the persistent buffer only when this run needs more room than
+                // it currently has; otherwise reuse it and just update its contents.
+                if self.vbuf_capacity < instance_data.len() {
+                    self.vbuf = Some(
+                        factory
+                            .create_buffer(
+                                instance_data.len(),
+                                buffer::Role::Vertex,
+                                Usage::Dynamic,
+                                Bind::TRANSFER_DST,
+                            )
+                            .expect("Unable to create persistent vertex buffer for `TextureBatch`"),
+                    );
+                    self.vbuf_capacity = instance_data.len();
+                }
+                let vbuf = self
+                    .vbuf
+                    .as_ref()
+                    .expect("Persistent vertex buffer was not allocated");
+                encoder
+                    .update_buffer(vbuf, &instance_data, 0)
+                    .expect("Unable to update persistent vertex buffer for `TextureBatch`");
 
                 for _ in DrawFlat2D::attributes() {
-                    effect.data.vertex_bufs.push(vbuf.raw().clone());
+                    active_effect.data.vertex_bufs.push(vbuf.raw().clone());
                 }
 
-                effect.draw(
+                active_effect.draw(
                     &Slice {
                         start: 0,
                         end: 6,
@@ -561,7 +1804,7 @@ impl<N: Real> TextureBatch<N> {
                     encoder,
                 );
 
-                effect.clear();
+                active_effect.clear();
 
                 num_instances = 0;
                 instance_data.clear();